@@ -1,7 +1,9 @@
 use super::{Error, Result};
+use jiff::tz::TimeZone;
 use jiff::Zoned;
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
 use std::num::NonZeroU32;
+use std::path::Path;
 use std::{path::PathBuf, str::FromStr};
 
 /// Program configuration.
@@ -32,8 +34,22 @@ pub struct Config {
     /// longitude
     pub lon: f64,
 
+    /// IANA time zone name (e.g. `America/New_York`) to use for all zoned arithmetic.
+    ///
+    /// Falls back to the system time zone when absent, which is wrong whenever `lat`/`lon` are
+    /// in a different zone than the machine running the tool (e.g. remote or debug use).
+    #[serde(default, deserialize_with = "deserialize_tz")]
+    pub tz: Option<TimeZone>,
+
     /// Wallpaper configuration
     pub wallpaper: Wallpaper,
+
+    /// Daemon mode configuration.
+    ///
+    /// Only needed when running via `--daemon`; absent entirely when just printing a single
+    /// index.
+    #[serde(default)]
+    pub daemon: Option<Daemon>,
 }
 
 impl Config {
@@ -45,6 +61,49 @@ impl Config {
             .join(env!("CARGO_PKG_NAME"))
             .join("config.toml")
     }
+
+    /// Write a commented starter config file to `path`, creating parent directories as needed.
+    ///
+    /// Refuses to overwrite an existing file unless `force` is set.
+    pub fn generate_default(path: &Path, lat: f64, lon: f64, force: bool) -> Result<()> {
+        if path.exists() && !force {
+            return Err(Error::Config(
+                "refusing to overwrite existing config file (pass force to overwrite)",
+            ));
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let contents = format!(
+            r#"# Latitude and longitude of the location to calculate sunrise/sunset for.
+lat = {lat}
+lon = {lon}
+
+[wallpaper]
+# Number of images to use during the day, numbered chronologically starting from 1.
+day_images = 13
+# Number of images to use at night, numbered after the day images, in chronological order.
+night_images = 3
+
+# Directory containing the wallpaper images, and the filename pattern used to find them.
+# Uncomment these, and set `set_wallpaper = true`, to set the desktop background directly
+# instead of just printing the image index.
+# image_dir = "/home/user/Pictures/wallpaper"
+# filename_pattern = "wallpaper-{{n}}.jpg"
+# set_wallpaper = true
+
+# How to pick the image for the current time: "linear" (default) interpolates between sunrise
+# and sunset; "elevation" picks by the sun's angle above the horizon.
+# mode = "linear"
+"#
+        );
+
+        std::fs::write(path, contents)?;
+
+        Ok(())
+    }
 }
 
 /// Get the current time.
@@ -52,6 +111,16 @@ fn default_time() -> Zoned {
     Zoned::try_from(std::time::SystemTime::now()).unwrap()
 }
 
+/// Deserialize an IANA time zone name (e.g. `America/New_York`) into a [`TimeZone`].
+fn deserialize_tz<'de, D>(deserializer: D) -> std::result::Result<Option<TimeZone>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let name: Option<String> = Option::deserialize(deserializer)?;
+    name.map(|name| TimeZone::get(&name).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
 impl TryFrom<PathBuf> for Config {
     type Error = Error;
 
@@ -82,6 +151,66 @@ pub struct Wallpaper {
     ///
     /// These should be numbered after the day images, in chronological order.
     pub night_images: NonZeroU32,
+
+    /// Directory containing the wallpaper images.
+    ///
+    /// Required when `set_wallpaper` is enabled.
+    #[serde(default)]
+    pub image_dir: Option<PathBuf>,
+
+    /// Filename pattern used to find the image for a given index, with `{n}` standing in for
+    /// the 1-based image index.
+    #[serde(default = "default_filename_pattern")]
+    pub filename_pattern: String,
+
+    /// Whether to set the desktop background directly, instead of only printing the index.
+    #[serde(default)]
+    pub set_wallpaper: bool,
+
+    /// Image selection mode.
+    #[serde(default)]
+    pub mode: SelectionMode,
+}
+
+fn default_filename_pattern() -> String {
+    "wallpaper-{n}.jpg".to_string()
+}
+
+/// How to pick the image to use for the current time.
+#[derive(Debug, Default, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectionMode {
+    /// Interpolate linearly between sunrise and sunset (and the adjoining nights).
+    #[default]
+    Linear,
+
+    /// Select by the sun's elevation angle above or below the horizon.
+    Elevation,
+}
+
+/// Daemon mode configuration.
+#[derive(Debug, Deserialize)]
+pub struct Daemon {
+    /// Upper bound, in seconds, on how long to sleep before recomputing the image index, even
+    /// if the next scheduled boundary is farther away.
+    ///
+    /// Guards against the system clock jumping forward unexpectedly (e.g. resuming from
+    /// suspend), by forcing a periodic recheck instead of trusting a single precomputed sleep
+    /// target.
+    #[serde(default = "default_max_sleep_secs")]
+    pub max_sleep_secs: u64,
+}
+
+impl Default for Daemon {
+    fn default() -> Self {
+        Self {
+            max_sleep_secs: default_max_sleep_secs(),
+        }
+    }
+}
+
+fn default_max_sleep_secs() -> u64 {
+    3600
 }
 
 #[cfg(test)]
@@ -104,4 +233,94 @@ mod tests {
             toml::de::Error { .. }
         ));
     }
+
+    /// A scratch directory under the system temp dir, removed when dropped.
+    struct ScratchDir(PathBuf);
+
+    impl ScratchDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "dynamic_wallpaper-test-{name}-{}",
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for ScratchDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn generate_default_creates_missing_parent_dirs() {
+        let dir = ScratchDir::new("create-parents");
+        let path = dir.0.join("nested").join("config.toml");
+
+        Config::generate_default(&path, 12.34, -98.76, false).unwrap();
+
+        assert!(path.is_file());
+    }
+
+    #[test]
+    fn generate_default_refuses_to_overwrite_without_force() {
+        let dir = ScratchDir::new("refuse-overwrite");
+        let path = dir.0.join("config.toml");
+
+        Config::generate_default(&path, 12.34, -98.76, false).unwrap();
+        let original = std::fs::read_to_string(&path).unwrap();
+
+        let err = Config::generate_default(&path, 0.0, 0.0, false).unwrap_err();
+        assert!(matches!(err, Error::Config(_)));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), original);
+    }
+
+    #[test]
+    fn config_with_valid_tz() {
+        let config = r#"
+            lat = 12.34
+            lon = -98.76
+            tz = "America/New_York"
+
+            [wallpaper]
+            day_images = 13
+            night_images = 3
+        "#;
+
+        let config: Config = config.parse().unwrap();
+        assert_eq!(config.tz, Some(TimeZone::get("America/New_York").unwrap()));
+    }
+
+    #[test]
+    fn config_with_invalid_tz() {
+        let config = r#"
+            lat = 12.34
+            lon = -98.76
+            tz = "Not/A_Zone"
+
+            [wallpaper]
+            day_images = 13
+            night_images = 3
+        "#;
+
+        assert!(matches!(
+            config.parse::<Config>().unwrap_err(),
+            toml::de::Error { .. }
+        ));
+    }
+
+    #[test]
+    fn generate_default_overwrites_with_force() {
+        let dir = ScratchDir::new("force-overwrite");
+        let path = dir.0.join("config.toml");
+
+        Config::generate_default(&path, 12.34, -98.76, false).unwrap();
+        Config::generate_default(&path, 0.0, 0.0, true).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("lat = 0"));
+        assert!(contents.contains("lon = 0"));
+    }
 }
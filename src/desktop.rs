@@ -0,0 +1,181 @@
+//! Setting the desktop background directly.
+//!
+//! Resolves an image index to a file path and asks the current desktop environment to display it
+//! as the wallpaper, rather than leaving that to the caller. This covers the common Linux
+//! desktops: GNOME (`gsettings`), KDE Plasma (`plasma-apply-wallpaperimage`), and sway/wlroots
+//! compositors (`swaymsg`).
+
+use crate::{Error, Result, Wallpaper};
+use std::process::Command;
+
+/// Resolve `index` to a path under `wallpaper.image_dir` and set it as the desktop background.
+///
+/// The desktop environment is picked from `XDG_CURRENT_DESKTOP`. Returns [`Error::Config`] if
+/// `image_dir` is unset or the desktop environment isn't one of the supported ones.
+pub fn set_wallpaper(index: i64, wallpaper: &Wallpaper) -> Result<()> {
+    let path = resolve_image_path(index, wallpaper)?;
+
+    let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+    let desktop = desktop.to_ascii_lowercase();
+
+    let status = if desktop.contains("gnome") {
+        Command::new("gsettings")
+            .args([
+                "set",
+                "org.gnome.desktop.background",
+                "picture-uri",
+                &format!("file://{}", percent_encode_path(&path)),
+            ])
+            .status()?
+    } else if desktop.contains("kde") {
+        Command::new("plasma-apply-wallpaperimage")
+            .arg(&path)
+            .status()?
+    } else if desktop.contains("sway") || desktop.contains("wlroots") {
+        Command::new("swaymsg")
+            .arg(format!("output * bg \"{}\" fill", quote_sway_path(&path)))
+            .status()?
+    } else {
+        return Err(Error::Config(
+            "unsupported desktop environment for set_wallpaper (XDG_CURRENT_DESKTOP)",
+        ));
+    };
+
+    if !status.success() {
+        return Err(Error::Config("failed to set desktop wallpaper"));
+    }
+
+    Ok(())
+}
+
+/// Resolve `index` to a path under `wallpaper.image_dir`.
+///
+/// Returns [`Error::Config`] if `image_dir` is unset.
+fn resolve_image_path(index: i64, wallpaper: &Wallpaper) -> Result<std::path::PathBuf> {
+    let image_dir = wallpaper.image_dir.as_ref().ok_or(Error::Config(
+        "wallpaper.image_dir is required when set_wallpaper is enabled",
+    ))?;
+    let filename = wallpaper
+        .filename_pattern
+        .replace("{n}", &index.to_string());
+
+    Ok(image_dir.join(filename))
+}
+
+/// Percent-encode `path` for use in a `file://` URI, per the unreserved characters in
+/// [RFC 3986](https://www.rfc-editor.org/rfc/rfc3986#section-2.3).
+///
+/// Avoids pulling in a dependency for this one call site; `gsettings` only needs the handful of
+/// characters that are common in real wallpaper paths (spaces above all) escaped correctly.
+fn percent_encode_path(path: &std::path::Path) -> String {
+    path.to_string_lossy()
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// Escape `path` for use inside a double-quoted token in a `swaymsg` IPC command.
+///
+/// Sway's command parser tokenizes on whitespace, so an unquoted path containing a space would
+/// be split into multiple arguments; quoting keeps it as one token, and backslashes/quotes in the
+/// path itself need escaping so they don't end the quoted token early.
+fn quote_sway_path(path: &std::path::Path) -> String {
+    path.to_string_lossy()
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::num::NonZeroU32;
+    use std::path::{Path, PathBuf};
+
+    fn wallpaper(image_dir: Option<&str>, filename_pattern: &str) -> Wallpaper {
+        Wallpaper {
+            day_images: NonZeroU32::new(13).unwrap(),
+            night_images: NonZeroU32::new(3).unwrap(),
+            image_dir: image_dir.map(PathBuf::from),
+            filename_pattern: filename_pattern.to_string(),
+            set_wallpaper: true,
+            mode: crate::SelectionMode::Linear,
+        }
+    }
+
+    #[test]
+    fn resolve_image_path_substitutes_index() {
+        let wallpaper = wallpaper(
+            Some("/home/user/Pictures/My Wallpapers"),
+            "wallpaper-{n}.jpg",
+        );
+
+        let path = resolve_image_path(3, &wallpaper).unwrap();
+
+        assert_eq!(
+            path,
+            Path::new("/home/user/Pictures/My Wallpapers/wallpaper-3.jpg")
+        );
+    }
+
+    #[test]
+    fn resolve_image_path_requires_image_dir() {
+        let wallpaper = wallpaper(None, "wallpaper-{n}.jpg");
+
+        assert!(matches!(
+            resolve_image_path(3, &wallpaper).unwrap_err(),
+            Error::Config(_)
+        ));
+    }
+
+    #[test]
+    fn percent_encode_path_escapes_spaces() {
+        let path = Path::new("/home/user/My Wallpapers/wallpaper-3.jpg");
+
+        assert_eq!(
+            percent_encode_path(path),
+            "/home/user/My%20Wallpapers/wallpaper-3.jpg"
+        );
+    }
+
+    #[test]
+    fn percent_encode_path_escapes_non_ascii() {
+        let path = Path::new("/home/user/Papéis de Parede/1.jpg");
+
+        assert_eq!(
+            percent_encode_path(path),
+            "/home/user/Pap%C3%A9is%20de%20Parede/1.jpg"
+        );
+    }
+
+    #[test]
+    fn percent_encode_path_leaves_unreserved_characters_alone() {
+        let path = Path::new("/home/user/wallpaper_dir-1/wallpaper-3.jpg");
+
+        assert_eq!(percent_encode_path(path), path.to_str().unwrap());
+    }
+
+    #[test]
+    fn quote_sway_path_escapes_quotes_and_backslashes() {
+        let path = Path::new(r#"/home/user/weird"dir\name/1.jpg"#);
+
+        assert_eq!(
+            quote_sway_path(path),
+            r#"/home/user/weird\"dir\\name/1.jpg"#
+        );
+    }
+
+    #[test]
+    fn quote_sway_path_leaves_spaces_unescaped() {
+        let path = Path::new("/home/user/My Wallpapers/wallpaper-3.jpg");
+
+        assert_eq!(
+            quote_sway_path(path),
+            "/home/user/My Wallpapers/wallpaper-3.jpg"
+        );
+    }
+}
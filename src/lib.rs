@@ -4,31 +4,209 @@
 //! location. These are set in `~/.config/dynamic_wallpaper/config.toml`.
 
 mod config;
+mod desktop;
 mod error;
+mod solar;
 
-pub use self::config::{Config, Wallpaper};
+pub use self::config::{Config, Daemon, SelectionMode, Wallpaper};
+pub use self::desktop::set_wallpaper;
 pub use self::error::Error;
 
-use jiff::SpanArithmetic;
-use jiff::{Timestamp, ToSpan, Unit, Zoned, tz::TimeZone};
+use self::solar::SolarPosition;
+use jiff::{tz::TimeZone, Timestamp, ToSpan, Unit, Zoned};
 use std::path::PathBuf;
 
 /// Result type alias to handle errors.
 type Result<T> = std::result::Result<T, Error>;
 
+/// Upper bound, in seconds, applied to [`Daemon::max_sleep_secs`] before it's added to the
+/// current time, so a pathologically large (but schema-valid) config value can't overflow
+/// [`Zoned`]'s representable range. A year is far longer than the daemon would ever actually
+/// need to wait before its periodic recheck.
+const MAX_SLEEP_SECS_CAP: u64 = 365 * 24 * 60 * 60;
+
 /// Main entry point.
 pub fn run() -> Result<i64> {
     let config = get_config()?;
-    let now = config.now;
+    let tz = config.tz.unwrap_or_else(TimeZone::system);
+    let now = config.now.with_time_zone(tz.clone());
     let wallpaper = config.wallpaper;
 
-    let sun = Sun::new(&now, config.lat, config.lon);
+    let sun = Sun::new(&now, config.lat, config.lon, &tz);
 
     let image = get_image(&now, &sun, &wallpaper);
 
+    if wallpaper.set_wallpaper {
+        set_wallpaper(image, &wallpaper)?;
+    }
+
     Ok(image)
 }
 
+/// Run forever, recomputing the image index at each boundary and sleeping until the next one.
+///
+/// Rather than polling on a fixed interval, this sleeps until the exact instant the image index
+/// is expected to change next (the next per-image boundary from [`get_image`]'s interpolation, or
+/// the next sunrise/sunset, whichever comes first), then recomputes from scratch. [`Sun`] is
+/// recalculated whenever the date rolls over. The sleep target is never trusted blindly: each
+/// iteration re-reads the system clock, so a clock jump (e.g. suspend/resume) just triggers an
+/// earlier or later recompute instead of drifting out of sync.
+///
+/// `on_index` is called with each newly computed image index; this library leaves printing (or
+/// any other handling) to the caller, the same way [`run`] only returns its index rather than
+/// printing it.
+pub fn run_daemon(mut on_index: impl FnMut(i64)) -> Result<()> {
+    let config = get_config()?;
+    let tz = config.tz.unwrap_or_else(TimeZone::system);
+    let daemon = config.daemon.unwrap_or_default();
+    let wallpaper = config.wallpaper;
+
+    let mut sun = Sun::new(
+        &Zoned::now().with_time_zone(tz.clone()),
+        config.lat,
+        config.lon,
+        &tz,
+    );
+
+    loop {
+        let now = Zoned::now().with_time_zone(tz.clone());
+        if now.date() != sun.sunrise.date() {
+            sun = Sun::new(&now, config.lat, config.lon, &tz);
+        }
+
+        let index = get_image(&now, &sun, &wallpaper);
+        on_index(index);
+
+        if wallpaper.set_wallpaper {
+            set_wallpaper(index, &wallpaper)?;
+        }
+
+        let boundary = next_boundary(&now, &sun, &wallpaper);
+        // Clamp to a sane cap before adding: `max_sleep_secs` is just a periodic-recheck safety
+        // net, so a misconfigured huge value should fall back to that cap instead of overflowing
+        // `Zoned`'s representable range.
+        let max_sleep_secs = daemon.max_sleep_secs.min(MAX_SLEEP_SECS_CAP);
+        let max_sleep_until = now
+            .checked_add(i64::try_from(max_sleep_secs).unwrap().seconds())
+            .unwrap();
+        let sleep_until = if boundary < max_sleep_until {
+            boundary
+        } else {
+            max_sleep_until
+        };
+
+        let sleep_secs = now
+            .until(&sleep_until)
+            .unwrap()
+            .total(Unit::Second)
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_secs_f64(sleep_secs.max(0.0)));
+    }
+}
+
+/// Compute the exact instant at which [`get_image`]'s return value will next change.
+fn next_boundary(now: &Zoned, sun: &Sun, wallpaper: &Wallpaper) -> Zoned {
+    match wallpaper.mode {
+        SelectionMode::Linear => next_boundary_linear(now, sun, wallpaper),
+        SelectionMode::Elevation => next_boundary_elevation(now, sun, wallpaper),
+    }
+}
+
+/// Compute the next boundary for [`get_image_linear`]: whichever comes first, the next
+/// per-image boundary within the current sunrise/sunset period, or the sunrise/sunset boundary
+/// itself.
+fn next_boundary_linear(now: &Zoned, sun: &Sun, wallpaper: &Wallpaper) -> Zoned {
+    let Sun {
+        sunrise,
+        sunset,
+        prev_sunset,
+        next_sunrise,
+        ..
+    } = sun;
+    let length_of_daytime = sunrise.until(sunset).unwrap();
+    let day_image_count = f64::from(wallpaper.day_images.get());
+    let night_image_count = f64::from(wallpaper.night_images.get());
+
+    let seconds_per_day_image = length_of_daytime.total(Unit::Second).unwrap() / day_image_count;
+
+    match TimePeriod::new(now, sun) {
+        TimePeriod::BeforeSunrise => {
+            let length_of_current_night = prev_sunset.until(sunrise).unwrap();
+            let seconds_per_night_image =
+                length_of_current_night.total(Unit::Second).unwrap() / night_image_count;
+            let seconds_into_current_night =
+                prev_sunset.until(now).unwrap().total(Unit::Second).unwrap();
+            let seconds_into_current_image = seconds_into_current_night % seconds_per_night_image;
+            let seconds_until_next_image = seconds_per_night_image - seconds_into_current_image;
+            let candidate = now
+                .checked_add((seconds_until_next_image.ceil() as i64).seconds())
+                .unwrap();
+            if candidate < *sunrise {
+                candidate
+            } else {
+                sunrise.clone()
+            }
+        }
+        TimePeriod::DayTime => {
+            let seconds_since_sunrise = sunrise.until(now).unwrap().total(Unit::Second).unwrap();
+            let seconds_into_current_image = seconds_since_sunrise % seconds_per_day_image;
+            let seconds_until_next_image = seconds_per_day_image - seconds_into_current_image;
+            let candidate = now
+                .checked_add((seconds_until_next_image.ceil() as i64).seconds())
+                .unwrap();
+            if candidate < *sunset {
+                candidate
+            } else {
+                sunset.clone()
+            }
+        }
+        TimePeriod::AfterSunset => {
+            let length_of_current_night = sunset.until(next_sunrise).unwrap();
+            let seconds_per_night_image =
+                length_of_current_night.total(Unit::Second).unwrap() / night_image_count;
+            let seconds_since_sunset = sunset.until(now).unwrap().total(Unit::Second).unwrap();
+            let seconds_into_current_image = seconds_since_sunset % seconds_per_night_image;
+            let seconds_until_next_image = seconds_per_night_image - seconds_into_current_image;
+            now.checked_add((seconds_until_next_image.ceil() as i64).seconds())
+                .unwrap()
+        }
+    }
+}
+
+/// Compute the next boundary for [`get_image_elevation`] by sampling forward in fixed steps
+/// until the index changes, then refining to the second with a binary search.
+///
+/// Unlike the linear mode, elevation has no closed-form inverse, so the boundary is found
+/// numerically rather than computed directly.
+fn next_boundary_elevation(now: &Zoned, sun: &Sun, wallpaper: &Wallpaper) -> Zoned {
+    const STEP: i64 = 60;
+    const MAX_STEPS: i64 = 24 * 60;
+
+    let current_index = get_image_elevation(now, sun, wallpaper);
+
+    let mut lo = now.clone();
+    let mut hi = now.clone();
+    for _ in 0..MAX_STEPS {
+        hi = hi.checked_add(STEP.seconds()).unwrap();
+        if get_image_elevation(&hi, sun, wallpaper) != current_index {
+            break;
+        }
+        lo = hi.clone();
+    }
+
+    while lo.until(&hi).unwrap().total(Unit::Second).unwrap() > 1.0 {
+        let half = (lo.until(&hi).unwrap().total(Unit::Second).unwrap() / 2.0) as i64;
+        let mid = lo.checked_add(half.seconds()).unwrap();
+        if get_image_elevation(&mid, sun, wallpaper) == current_index {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+
+    hi
+}
+
 fn get_config() -> Result<Config> {
     let filename = std::env::var("DYNAMIC_WALLPAPER_CONFIG").map_or_else(
         |_| {
@@ -74,48 +252,98 @@ fn get_config() -> Result<Config> {
 /// - D: next sunrise
 ///
 /// The sunrise and sunset times are calculated for the current day, and given to the `sun`
-/// ([`Sun`]) parameter. Since we don't know the time of the previous sunset (A) or the next
-/// sunrise (B), we have to make an approximation: assuming the day is 24 hours long, get the
-/// difference of 24h - daylight. This becomes our nighttime duration.
+/// ([`Sun`]) parameter, which also carries the previous sunset (A) and next sunrise (D), queried
+/// directly rather than approximated from the current day's daylight length. This keeps the two
+/// night-image ranges correct even when consecutive nights differ substantially in length, such
+/// as near the solstices or at high latitudes.
 fn get_image(now: &Zoned, sun: &Sun, wallpaper: &Wallpaper) -> i64 {
-    let Sun { sunrise, sunset } = sun;
+    match wallpaper.mode {
+        SelectionMode::Linear => get_image_linear(now, sun, wallpaper),
+        SelectionMode::Elevation => get_image_elevation(now, sun, wallpaper),
+    }
+}
+
+/// Linearly interpolate between sunrise and sunset (and the adjoining nights) to pick an image.
+fn get_image_linear(now: &Zoned, sun: &Sun, wallpaper: &Wallpaper) -> i64 {
+    let Sun {
+        sunrise,
+        sunset,
+        prev_sunset,
+        next_sunrise,
+        ..
+    } = sun;
     let length_of_daytime = sunrise.until(sunset).unwrap();
-    let length_of_nighttime = 1
-        .day()
-        .checked_sub(SpanArithmetic::from(length_of_daytime).days_are_24_hours())
-        .unwrap();
     let day_image_count = f64::from(wallpaper.day_images.get());
     let night_image_count = f64::from(wallpaper.night_images.get());
 
-    let seconds_per_day_image = || length_of_daytime.total(Unit::Second).unwrap() / day_image_count;
-    let seconds_per_night_image =
-        || length_of_nighttime.total(Unit::Second).unwrap() / night_image_count;
+    let seconds_per_day_image = length_of_daytime.total(Unit::Second).unwrap() / day_image_count;
 
-    let time_period = TimePeriod::new(now, sun);
-
-    let index = match time_period {
+    let index = match TimePeriod::new(now, sun) {
         TimePeriod::BeforeSunrise => {
-            let time_until_sunrise = now.until(sunrise).unwrap();
-            let time_into_current_night = length_of_nighttime
-                .checked_sub((&time_until_sunrise, now))
-                .unwrap();
-            let seconds_into_current_night = time_into_current_night.total(Unit::Second).unwrap();
+            let length_of_current_night = prev_sunset.until(sunrise).unwrap();
+            let seconds_per_night_image =
+                length_of_current_night.total(Unit::Second).unwrap() / night_image_count;
+            let seconds_into_current_night =
+                prev_sunset.until(now).unwrap().total(Unit::Second).unwrap();
 
-            day_image_count + seconds_into_current_night / seconds_per_night_image()
+            day_image_count + seconds_into_current_night / seconds_per_night_image
         }
         TimePeriod::DayTime => {
             let time_since_sunrise = sunrise.until(now).unwrap();
-            time_since_sunrise.total(Unit::Second).unwrap() / seconds_per_day_image()
+            time_since_sunrise.total(Unit::Second).unwrap() / seconds_per_day_image
         }
         TimePeriod::AfterSunset => {
+            let length_of_current_night = sunset.until(next_sunrise).unwrap();
+            let seconds_per_night_image =
+                length_of_current_night.total(Unit::Second).unwrap() / night_image_count;
             let seconds_since_sunset = sunset.until(now).unwrap().total(Unit::Second).unwrap();
-            day_image_count + seconds_since_sunset / seconds_per_night_image()
+
+            day_image_count + seconds_since_sunset / seconds_per_night_image
         }
     };
 
     index as i64 + 1
 }
 
+/// Elevation below which the sky is considered fully dark, for image-selection purposes: the
+/// end of astronomical twilight.
+const NIGHT_FLOOR_DEG: f64 = -18.0;
+
+/// Pick an image from the sun's actual elevation above (or below) the horizon, rather than by
+/// linearly interpolating between sunrise and sunset.
+///
+/// Elevation at or above the horizon is scaled against the elevation at solar noon and mapped
+/// onto the day-image range; elevation below the horizon is scaled against [`NIGHT_FLOOR_DEG`]
+/// (roughly the end of astronomical twilight) and mapped onto the night-image range.
+fn get_image_elevation(now: &Zoned, sun: &Sun, wallpaper: &Wallpaper) -> i64 {
+    let day_image_count = f64::from(wallpaper.day_images.get());
+    let night_image_count = f64::from(wallpaper.night_images.get());
+
+    let elevation = SolarPosition::new(now, sun.lat, sun.lon).elevation_deg;
+
+    let solar_noon = sun
+        .sunrise
+        .checked_add(
+            sun.sunrise
+                .until(&sun.sunset)
+                .unwrap()
+                .checked_div(2)
+                .unwrap(),
+        )
+        .unwrap();
+    let max_elevation = SolarPosition::new(&solar_noon, sun.lat, sun.lon).elevation_deg;
+
+    let index = if elevation >= 0.0 {
+        let fraction = (elevation / max_elevation).clamp(0.0, 1.0);
+        fraction * (day_image_count - 1.0)
+    } else {
+        let fraction = (elevation / NIGHT_FLOOR_DEG).clamp(0.0, 1.0);
+        day_image_count + fraction * (night_image_count - 1.0)
+    };
+
+    index as i64 + 1
+}
+
 /// Sunrise and sunset times.
 #[derive(Debug)]
 struct Sun {
@@ -124,28 +352,55 @@ struct Sun {
 
     /// Today's sunset.
     sunset: Zoned,
+
+    /// Yesterday's sunset: the true start of the night ending at `sunrise`.
+    prev_sunset: Zoned,
+
+    /// Tomorrow's sunrise: the true end of the night starting at `sunset`.
+    next_sunrise: Zoned,
+
+    /// Latitude, carried along for elevation-based image selection.
+    lat: f64,
+
+    /// Longitude, carried along for elevation-based image selection.
+    lon: f64,
 }
 
 impl Sun {
-    /// Get the time of sunrise and sunset depending on the date and location.
-    fn new(date: &Zoned, lat: f64, lon: f64) -> Self {
-        let (sunrise, sunset) = {
-            let (sunrise, sunset) =
-                sunrise::sunrise_sunset(lat, lon, date.year(), date.month(), date.day());
-            (
-                Timestamp::new(sunrise, 0)
-                    .unwrap()
-                    .to_zoned(TimeZone::system()),
-                Timestamp::new(sunset, 0)
-                    .unwrap()
-                    .to_zoned(TimeZone::system()),
-            )
-        };
+    /// Get the time of sunrise and sunset depending on the date, location, and time zone.
+    ///
+    /// Also queries the adjoining days, so the previous sunset and next sunrise are known
+    /// exactly rather than approximated.
+    fn new(date: &Zoned, lat: f64, lon: f64, tz: &TimeZone) -> Self {
+        let (sunrise, sunset) = sun_times(date, lat, lon, tz);
+
+        let yesterday = date.checked_sub(1.day()).unwrap();
+        let tomorrow = date.checked_add(1.day()).unwrap();
+
+        let (_, prev_sunset) = sun_times(&yesterday, lat, lon, tz);
+        let (next_sunrise, _) = sun_times(&tomorrow, lat, lon, tz);
 
-        Self { sunrise, sunset }
+        Self {
+            sunrise,
+            sunset,
+            prev_sunset,
+            next_sunrise,
+            lat,
+            lon,
+        }
     }
 }
 
+/// Get the sunrise and sunset for `date` at the given location and time zone.
+fn sun_times(date: &Zoned, lat: f64, lon: f64, tz: &TimeZone) -> (Zoned, Zoned) {
+    let (sunrise, sunset) =
+        sunrise::sunrise_sunset(lat, lon, date.year(), date.month(), date.day());
+    (
+        Timestamp::new(sunrise, 0).unwrap().to_zoned(tz.clone()),
+        Timestamp::new(sunset, 0).unwrap().to_zoned(tz.clone()),
+    )
+}
+
 /// Time of day according to the sun.
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 enum TimePeriod {
@@ -197,6 +452,16 @@ mod tests {
             sunset: jiff::civil::datetime(2018, 8, 6, 20, 0, 0, 0)
                 .to_zoned(TimeZone::system())
                 .unwrap(),
+            // Same time of day as today's sunset/sunrise, so the adjoining nights are the same
+            // length as the 24h-minus-daylight approximation this replaces.
+            prev_sunset: jiff::civil::datetime(2018, 8, 5, 20, 0, 0, 0)
+                .to_zoned(TimeZone::system())
+                .unwrap(),
+            next_sunrise: jiff::civil::datetime(2018, 8, 7, 6, 0, 0, 0)
+                .to_zoned(TimeZone::system())
+                .unwrap(),
+            lat: 12.3456,
+            lon: -65.4321,
         };
     }
 
@@ -285,6 +550,10 @@ mod tests {
             static ref WALLPAPER: Wallpaper = Wallpaper {
                 day_images: NonZeroU32::new(13).unwrap(),
                 night_images: NonZeroU32::new(3).unwrap(),
+                image_dir: None,
+                filename_pattern: "wallpaper-{n}.jpg".to_string(),
+                set_wallpaper: false,
+                mode: SelectionMode::Linear,
             };
         }
 
@@ -367,6 +636,10 @@ mod tests {
             static ref WALLPAPER: Wallpaper = Wallpaper {
                 day_images: NonZeroU32::new(3).unwrap(),
                 night_images: NonZeroU32::new(1).unwrap(),
+                image_dir: None,
+                filename_pattern: "wallpaper-{n}.jpg".to_string(),
+                set_wallpaper: false,
+                mode: SelectionMode::Linear,
             };
         }
 
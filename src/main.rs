@@ -1,12 +1,39 @@
 //! Dynamic Wallpaper
 
+use dynamic_wallpaper::Config;
+
 /// Main binary point of entry
 fn main() {
-    match dynamic_wallpaper::run() {
-        Ok(index) => println!("{index}"),
-        Err(e) => {
-            eprintln!("{e}");
-            std::process::exit(1);
-        }
+    let args: Vec<String> = std::env::args().collect();
+
+    let result = if args.get(1).map(String::as_str) == Some("init") {
+        run_init(&args[2..])
+    } else if args.iter().any(|arg| arg == "--daemon") {
+        dynamic_wallpaper::run_daemon(|index| println!("{index}"))
+    } else {
+        dynamic_wallpaper::run().map(|index| println!("{index}"))
+    };
+
+    if let Err(e) = result {
+        eprintln!("{e}");
+        std::process::exit(1);
     }
 }
+
+/// Handle `dynamic_wallpaper init <lat> <lon> [--force]`: write a starter config file.
+fn run_init(args: &[String]) -> Result<(), dynamic_wallpaper::Error> {
+    let force = args.iter().any(|arg| arg == "--force");
+    let positional: Vec<&String> = args.iter().filter(|arg| *arg != "--force").collect();
+
+    let [lat, lon] = positional[..] else {
+        eprintln!("usage: dynamic_wallpaper init <lat> <lon> [--force]");
+        std::process::exit(1);
+    };
+    let (Ok(lat), Ok(lon)) = (lat.parse::<f64>(), lon.parse::<f64>()) else {
+        eprintln!("usage: dynamic_wallpaper init <lat> <lon> [--force]");
+        eprintln!("lat and lon must both be numbers");
+        std::process::exit(1);
+    };
+
+    Config::generate_default(&Config::default_path(), lat, lon, force)
+}
@@ -0,0 +1,94 @@
+//! The sun's elevation angle above the horizon.
+//!
+//! Used by the `elevation` [`SelectionMode`](crate::SelectionMode) to pick an image by how high
+//! the sun actually is in the sky, rather than by linearly interpolating between sunrise and
+//! sunset. Implements the NOAA solar position approximation; see
+//! <https://gml.noaa.gov/grad/solcalc/solareqns.PDF>.
+
+use jiff::Zoned;
+use std::f64::consts::PI;
+
+/// The sun's elevation above the horizon, in degrees, at a given time and place.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct SolarPosition {
+    /// Degrees above the horizon. Negative when the sun is below the horizon.
+    pub elevation_deg: f64,
+}
+
+impl SolarPosition {
+    /// Compute the sun's elevation at `now` for the given latitude/longitude.
+    pub fn new(now: &Zoned, lat: f64, lon: f64) -> Self {
+        let day_of_year = f64::from(now.day_of_year());
+        let hour = f64::from(now.hour())
+            + f64::from(now.minute()) / 60.0
+            + f64::from(now.second()) / 3600.0;
+
+        let gamma = 2.0 * PI / 365.0 * (day_of_year - 1.0 + (hour - 12.0) / 24.0);
+
+        // Equation of time, in minutes.
+        let eqtime = 229.18
+            * (0.000_075 + 0.001_868 * gamma.cos()
+                - 0.032_077 * gamma.sin()
+                - 0.014_615 * (2.0 * gamma).cos()
+                - 0.040_849 * (2.0 * gamma).sin());
+
+        // Solar declination, in radians.
+        let decl = 0.006_918 - 0.399_912 * gamma.cos() + 0.070_257 * gamma.sin()
+            - 0.006_758 * (2.0 * gamma).cos()
+            + 0.000_907 * (2.0 * gamma).sin()
+            - 0.002_697 * (3.0 * gamma).cos()
+            + 0.001_48 * (3.0 * gamma).sin();
+
+        let utc_offset_minutes = f64::from(now.offset().seconds()) / 60.0;
+        let true_solar_time = hour * 60.0 + eqtime + 4.0 * lon - utc_offset_minutes;
+        let hour_angle_deg = true_solar_time / 4.0 - 180.0;
+
+        let lat_rad = lat.to_radians();
+        let hour_angle_rad = hour_angle_deg.to_radians();
+
+        let cos_zenith =
+            lat_rad.sin() * decl.sin() + lat_rad.cos() * decl.cos() * hour_angle_rad.cos();
+        let elevation_deg = 90.0 - cos_zenith.clamp(-1.0, 1.0).acos().to_degrees();
+
+        Self { elevation_deg }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jiff::tz::TimeZone;
+
+    #[test]
+    fn near_ninety_at_solar_noon_on_equinox_at_equator() {
+        // 2023-03-20 is the March equinox; at local solar noon on the equator the sun should be
+        // almost directly overhead. The equation of time is about -8 minutes that day, so solar
+        // noon at longitude 0 falls at about 12:08 UTC.
+        let now = jiff::civil::datetime(2023, 3, 20, 12, 8, 0, 0)
+            .to_zoned(TimeZone::UTC)
+            .unwrap();
+
+        let position = SolarPosition::new(&now, 0.0, 0.0);
+
+        assert!(
+            position.elevation_deg > 89.0,
+            "expected near-zenith elevation, got {}",
+            position.elevation_deg
+        );
+    }
+
+    #[test]
+    fn negative_at_local_midnight() {
+        let now = jiff::civil::datetime(2023, 3, 20, 0, 0, 0, 0)
+            .to_zoned(TimeZone::UTC)
+            .unwrap();
+
+        let position = SolarPosition::new(&now, 0.0, 0.0);
+
+        assert!(
+            position.elevation_deg < 0.0,
+            "expected sun below horizon at midnight, got {}",
+            position.elevation_deg
+        );
+    }
+}